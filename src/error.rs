@@ -0,0 +1,48 @@
+#[cfg(feature = "std")]
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use core::fmt;
+
+/// An error encountered while encoding or decoding a wire protocol message.
+#[derive(Debug)]
+pub enum Error {
+    /// A value was too large to be represented in the wire format.
+    TooLarge,
+    /// A string contained an embedded NUL byte, which cannot be represented
+    /// in a Postgres C-string.
+    EmbeddedNul,
+    /// A string was not valid UTF-8.
+    Utf8,
+    /// An unrecognized message tag byte.
+    UnknownTag(u8),
+    /// An unrecognized `AuthenticationOk`-family sub-code.
+    UnknownAuthenticationCode(i32),
+    /// The message body was malformed in some other way; the string
+    /// describes what was wrong with it.
+    InvalidMessage(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::TooLarge => write!(fmt, "value too large to transmit"),
+            Error::EmbeddedNul => write!(fmt, "string contains embedded null"),
+            Error::Utf8 => write!(fmt, "invalid UTF-8"),
+            Error::UnknownTag(tag) => write!(fmt, "unknown message tag `{}`", tag as char),
+            Error::UnknownAuthenticationCode(code) => {
+                write!(fmt, "unknown authentication message code `{}`", code)
+            }
+            Error::InvalidMessage(msg) => write!(fmt, "invalid message: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(_: std::io::Error) -> Error {
+        Error::InvalidMessage("truncated message")
+    }
+}
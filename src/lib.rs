@@ -0,0 +1,28 @@
+//! With the default `std` feature disabled, this crate builds against
+//! `core` and `alloc` only, for use in embedded or `no_std` async runtimes.
+//! `message::backend` and `scram` depend on `std::io`/`std::error::Error`
+//! and are only available with `std` enabled.
+#![cfg_attr(not(feature = "std"), no_std)]
+// This crate predates `?` and targets edition 2015 to keep `try!` available
+// throughout; the deprecation warning is expected noise, not a TODO.
+#![allow(deprecated)]
+
+extern crate byteorder;
+extern crate bytes;
+
+#[cfg(feature = "std")]
+extern crate base64;
+#[cfg(feature = "std")]
+extern crate hmac;
+#[cfg(feature = "std")]
+extern crate pbkdf2;
+#[cfg(feature = "std")]
+extern crate rand;
+#[cfg(feature = "std")]
+extern crate sha2;
+
+pub mod error;
+pub mod message;
+
+#[cfg(feature = "std")]
+pub mod scram;
@@ -0,0 +1,288 @@
+use byteorder::{ReadBytesExt, BigEndian};
+use std::io::Cursor;
+use std::str;
+
+use error::Error;
+use message::Oid;
+
+pub enum Message<'a> {
+    AuthenticationOk,
+    AuthenticationCleartextPassword,
+    AuthenticationMd5Password { salt: [u8; 4] },
+    AuthenticationSasl { mechanisms: Vec<&'a str> },
+    AuthenticationSaslContinue { data: &'a [u8] },
+    AuthenticationSaslFinal { data: &'a [u8] },
+    BackendKeyData { process_id: i32, secret_key: i32 },
+    ParameterStatus { name: &'a str, value: &'a str },
+    RowDescription { fields: Vec<Field<'a>> },
+    DataRow { values: Vec<Option<&'a [u8]>> },
+    CommandComplete { tag: &'a str },
+    ErrorResponse { fields: Vec<(u8, &'a str)> },
+    NoticeResponse { fields: Vec<(u8, &'a str)> },
+    ReadyForQuery { status: u8 },
+    ParameterDescription { types: Vec<Oid> },
+    ParseComplete,
+    BindComplete,
+    CloseComplete,
+    EmptyQueryResponse,
+    NoData,
+    PortalSuspended,
+    CopyInResponse { format: u8, column_formats: Vec<i16> },
+    CopyOutResponse { format: u8, column_formats: Vec<i16> },
+    CopyBothResponse { format: u8, column_formats: Vec<i16> },
+    CopyData { data: &'a [u8] },
+    CopyDone,
+}
+
+pub struct Field<'a> {
+    pub name: &'a str,
+    pub table_oid: Oid,
+    pub column_id: i16,
+    pub type_oid: Oid,
+    pub type_size: i16,
+    pub type_modifier: i32,
+    pub format: i16,
+}
+
+/// Parses a single message out of `buf`.
+///
+/// Returns `Ok(None)` if `buf` does not yet contain a full message, in which
+/// case the caller should read more data and try again. On success, returns
+/// the parsed message along with the number of bytes of `buf` it consumed.
+pub fn parse(buf: &[u8]) -> Result<Option<(Message<'_>, usize)>, Error> {
+    if buf.len() < 5 {
+        return Ok(None);
+    }
+
+    let tag = buf[0];
+    let len = Cursor::new(&buf[1..5]).read_i32::<BigEndian>().unwrap();
+    if len < 4 {
+        return Err(Error::InvalidMessage("invalid message length"));
+    }
+    let len = len as usize;
+
+    if buf.len() < 1 + len {
+        return Ok(None);
+    }
+
+    let body = &buf[5..1 + len];
+    let message = try!(parse_body(tag, body));
+    Ok(Some((message, 1 + len)))
+}
+
+fn parse_body(tag: u8, buf: &[u8]) -> Result<Message<'_>, Error> {
+    match tag {
+        b'R' => parse_authentication(buf),
+        b'K' => {
+            let mut buf = Cursor::new(buf);
+            let process_id = try!(buf.read_i32::<BigEndian>());
+            let secret_key = try!(buf.read_i32::<BigEndian>());
+            Ok(Message::BackendKeyData {
+                process_id,
+                secret_key,
+            })
+        }
+        b'S' => {
+            let mut it = buf.split(|&b| b == 0);
+            let name = try!(next_cstr(&mut it));
+            let value = try!(next_cstr(&mut it));
+            Ok(Message::ParameterStatus { name, value })
+        }
+        b'T' => parse_row_description(buf),
+        b'D' => parse_data_row(buf),
+        b'C' => Ok(Message::CommandComplete { tag: try!(cstr(buf)) }),
+        b'E' => Ok(Message::ErrorResponse { fields: try!(parse_fields(buf)) }),
+        b'N' => Ok(Message::NoticeResponse { fields: try!(parse_fields(buf)) }),
+        b'Z' => {
+            if buf.len() != 1 {
+                return Err(Error::InvalidMessage("invalid ReadyForQuery body"));
+            }
+            Ok(Message::ReadyForQuery { status: buf[0] })
+        }
+        b't' => parse_parameter_description(buf),
+        b'1' => Ok(Message::ParseComplete),
+        b'2' => Ok(Message::BindComplete),
+        b'3' => Ok(Message::CloseComplete),
+        b'I' => Ok(Message::EmptyQueryResponse),
+        b'n' => Ok(Message::NoData),
+        b's' => Ok(Message::PortalSuspended),
+        b'G' => parse_copy_response(buf, CopyResponseKind::In),
+        b'H' => parse_copy_response(buf, CopyResponseKind::Out),
+        b'W' => parse_copy_response(buf, CopyResponseKind::Both),
+        b'd' => Ok(Message::CopyData { data: buf }),
+        b'c' => Ok(Message::CopyDone),
+        _ => Err(Error::UnknownTag(tag)),
+    }
+}
+
+enum CopyResponseKind {
+    In,
+    Out,
+    Both,
+}
+
+fn parse_copy_response(buf: &[u8], kind: CopyResponseKind) -> Result<Message<'_>, Error> {
+    let mut buf = Cursor::new(buf);
+    let format = try!(buf.read_u8());
+    let num_columns = try!(buf.read_i16::<BigEndian>());
+    // `num_columns` comes straight off the wire and may be negative; don't
+    // let it dictate an allocation size.
+    let mut column_formats = Vec::new();
+    for _ in 0..num_columns {
+        column_formats.push(try!(buf.read_i16::<BigEndian>()));
+    }
+
+    Ok(match kind {
+        CopyResponseKind::In => Message::CopyInResponse { format, column_formats },
+        CopyResponseKind::Out => Message::CopyOutResponse { format, column_formats },
+        CopyResponseKind::Both => Message::CopyBothResponse { format, column_formats },
+    })
+}
+
+fn parse_authentication(buf: &[u8]) -> Result<Message<'_>, Error> {
+    let mut cursor = Cursor::new(buf);
+    let code = try!(cursor.read_i32::<BigEndian>());
+    let rest = &buf[4..];
+
+    match code {
+        0 => Ok(Message::AuthenticationOk),
+        3 => Ok(Message::AuthenticationCleartextPassword),
+        5 => {
+            if rest.len() != 4 {
+                return Err(Error::InvalidMessage("invalid AuthenticationMD5Password body"));
+            }
+            let mut salt = [0; 4];
+            salt.copy_from_slice(rest);
+            Ok(Message::AuthenticationMd5Password { salt })
+        }
+        10 => {
+            let mut mechanisms = vec![];
+            let mut it = rest.split(|&b| b == 0);
+            loop {
+                let s = try!(next_cstr(&mut it));
+                if s.is_empty() {
+                    break;
+                }
+                mechanisms.push(s);
+            }
+            Ok(Message::AuthenticationSasl { mechanisms })
+        }
+        11 => Ok(Message::AuthenticationSaslContinue { data: rest }),
+        12 => Ok(Message::AuthenticationSaslFinal { data: rest }),
+        _ => Err(Error::UnknownAuthenticationCode(code)),
+    }
+}
+
+fn parse_row_description(buf: &[u8]) -> Result<Message<'_>, Error> {
+    let mut cursor = Cursor::new(buf);
+    let num_fields = try!(cursor.read_i16::<BigEndian>());
+    // `num_fields` comes straight off the wire and may be negative; don't
+    // let it dictate an allocation size.
+    let mut fields = Vec::new();
+
+    for _ in 0..num_fields {
+        let pos = cursor.position() as usize;
+        let end = pos + try!(cursor.get_ref()[pos..]
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or(Error::InvalidMessage("unexpected end of row description")));
+        let name = try!(str::from_utf8(&cursor.get_ref()[pos..end]).map_err(|_| Error::Utf8));
+        cursor.set_position((end + 1) as u64);
+
+        let table_oid = try!(cursor.read_u32::<BigEndian>());
+        let column_id = try!(cursor.read_i16::<BigEndian>());
+        let type_oid = try!(cursor.read_u32::<BigEndian>());
+        let type_size = try!(cursor.read_i16::<BigEndian>());
+        let type_modifier = try!(cursor.read_i32::<BigEndian>());
+        let format = try!(cursor.read_i16::<BigEndian>());
+
+        fields.push(Field {
+            name,
+            table_oid,
+            column_id,
+            type_oid,
+            type_size,
+            type_modifier,
+            format,
+        });
+    }
+
+    Ok(Message::RowDescription { fields })
+}
+
+fn parse_data_row(buf: &[u8]) -> Result<Message<'_>, Error> {
+    let mut cursor = Cursor::new(buf);
+    let num_values = try!(cursor.read_i16::<BigEndian>());
+    // `num_values` comes straight off the wire and may be negative; don't
+    // let it dictate an allocation size.
+    let mut values = Vec::new();
+
+    for _ in 0..num_values {
+        let len = try!(cursor.read_i32::<BigEndian>());
+        if len < 0 {
+            values.push(None);
+            continue;
+        }
+        let pos = cursor.position() as usize;
+        let end = pos + len as usize;
+        if end > cursor.get_ref().len() {
+            return Err(Error::InvalidMessage("invalid data row value length"));
+        }
+        values.push(Some(&cursor.get_ref()[pos..end]));
+        cursor.set_position(end as u64);
+    }
+
+    Ok(Message::DataRow { values })
+}
+
+fn parse_parameter_description(buf: &[u8]) -> Result<Message<'_>, Error> {
+    let mut cursor = Cursor::new(buf);
+    let num_params = try!(cursor.read_i16::<BigEndian>());
+    // `num_params` comes straight off the wire and may be negative; don't
+    // let it dictate an allocation size.
+    let mut types = Vec::new();
+    for _ in 0..num_params {
+        types.push(try!(cursor.read_u32::<BigEndian>()));
+    }
+    Ok(Message::ParameterDescription { types })
+}
+
+fn parse_fields(buf: &[u8]) -> Result<Vec<(u8, &str)>, Error> {
+    let mut fields = vec![];
+    let mut pos = 0;
+
+    loop {
+        if pos >= buf.len() {
+            return Err(Error::InvalidMessage("unexpected end of field list"));
+        }
+        let field_type = buf[pos];
+        if field_type == 0 {
+            break;
+        }
+        pos += 1;
+
+        let end = pos + try!(buf[pos..]
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or(Error::InvalidMessage("unexpected end of field value")));
+        let value = try!(str::from_utf8(&buf[pos..end]).map_err(|_| Error::Utf8));
+        fields.push((field_type, value));
+        pos = end + 1;
+    }
+
+    Ok(fields)
+}
+
+fn cstr(buf: &[u8]) -> Result<&str, Error> {
+    let end = try!(buf.iter()
+            .position(|&b| b == 0)
+            .ok_or(Error::InvalidMessage("unexpected end of string")));
+    Ok(try!(str::from_utf8(&buf[..end]).map_err(|_| Error::Utf8)))
+}
+
+fn next_cstr<'a, I>(it: &mut I) -> Result<&'a str, Error>
+    where I: Iterator<Item = &'a [u8]>
+{
+    let bytes = try!(it.next().ok_or(Error::InvalidMessage("unexpected end of string list")));
+    Ok(try!(str::from_utf8(bytes).map_err(|_| Error::Utf8)))
+}
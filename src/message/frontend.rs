@@ -1,15 +1,75 @@
-use byteorder::{WriteBytesExt, BigEndian};
-use std::error::Error;
-use std::io::Cursor;
+use byteorder::{BigEndian, ByteOrder};
+use bytes::{BufMut, BytesMut};
 
+#[cfg(feature = "std")]
+use std::io::IoSlice;
+
+use error::Error;
 use message::Oid;
 
 pub trait Message {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>>;
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error>;
+}
+
+/// An alternative to [`Message`] for messages that carry a large payload
+/// (bulk `COPY` data, `bytea` bind parameters, etc), allowing the payload to
+/// be written directly to the socket via a vectored write rather than
+/// copied into the output buffer first.
+///
+/// Requires the `std` feature, since vectored I/O is a `std::io` concept.
+#[cfg(feature = "std")]
+pub trait MessageVectored {
+    /// Returns the segments that make up this message: some borrowed from
+    /// `scratch`, which this message uses to hold any bytes it has to
+    /// synthesize (tag, length prefixes, etc), and some borrowed directly
+    /// from the message's own payload. `scratch` is owned by the caller and
+    /// must remain alive as long as the returned slices are used.
+    fn write_vectored<'b>(&'b self, scratch: &'b mut BytesMut) -> Result<Vec<IoSlice<'b>>, Error>;
+}
+
+/// Appends a big-endian integer to `buf` without requiring `std::io::Write`,
+/// so the crate can serialize messages with only `core` and `alloc`.
+trait WriteExt {
+    fn write_i16(&mut self, n: i16);
+    fn write_u16(&mut self, n: u16);
+    fn write_i32(&mut self, n: i32);
+    fn write_u32(&mut self, n: u32);
 }
 
-fn write_body<F>(buf: &mut Vec<u8>, f: F) -> Result<(), Box<Error>>
-    where F: FnOnce(&mut Vec<u8>) -> Result<(), Box<Error>>
+impl WriteExt for BytesMut {
+    fn write_i16(&mut self, n: i16) {
+        let mut tmp = [0; 2];
+        BigEndian::write_i16(&mut tmp, n);
+        self.extend_from_slice(&tmp);
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        let mut tmp = [0; 2];
+        BigEndian::write_u16(&mut tmp, n);
+        self.extend_from_slice(&tmp);
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        let mut tmp = [0; 4];
+        BigEndian::write_i32(&mut tmp, n);
+        self.extend_from_slice(&tmp);
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        let mut tmp = [0; 4];
+        BigEndian::write_u32(&mut tmp, n);
+        self.extend_from_slice(&tmp);
+    }
+}
+
+/// Back-patches a 4-byte big-endian length slot at `base` (such as the header
+/// reserved by `write_body`), without depending on `std::io::Cursor`.
+fn patch_len(buf: &mut [u8], base: usize, len: i32) {
+    BigEndian::write_i32(&mut buf[base..base + 4], len);
+}
+
+fn write_body<F>(buf: &mut BytesMut, f: F) -> Result<(), Error>
+    where F: FnOnce(&mut BytesMut) -> Result<(), Error>
 {
     let base = buf.len();
     buf.extend_from_slice(&[0; 4]);
@@ -17,10 +77,16 @@ fn write_body<F>(buf: &mut Vec<u8>, f: F) -> Result<(), Box<Error>>
     try!(f(buf));
 
     let size = try!(i32::from_usize(buf.len() - base));
-    try!(Cursor::new(&mut buf[base..base + 4]).write_i32::<BigEndian>(size));
+    patch_len(buf, base, size);
     Ok(())
 }
 
+/// Whether a value being bound by [`BindBuilder`] represents SQL `NULL`.
+pub enum IsNull {
+    Yes,
+    No,
+}
+
 pub struct Bind<'a, T: 'a> {
     pub portal: &'a str,
     pub statement: &'a str,
@@ -32,37 +98,37 @@ pub struct Bind<'a, T: 'a> {
 impl<'a, T> Message for Bind<'a, T>
     where T: AsRef<[u8]>
 {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>> {
-        buf.push(b'B');
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'B');
 
         write_body(buf, |buf| {
             try!(buf.write_cstr(self.portal));
             try!(buf.write_cstr(self.statement));
 
             let num_formats = try!(u16::from_usize(self.formats.len()));
-            try!(buf.write_u16::<BigEndian>(num_formats));
+            buf.write_u16(num_formats);
             for &format in self.formats {
-                try!(buf.write_i16::<BigEndian>(format));
+                buf.write_i16(format);
             }
 
             let num_values = try!(u16::from_usize(self.values.len()));
-            try!(buf.write_u16::<BigEndian>(num_values));
+            buf.write_u16(num_values);
             for value in self.values {
                 match *value {
-                    None => try!(buf.write_i32::<BigEndian>(-1)),
+                    None => buf.write_i32(-1),
                     Some(ref value) => {
                         let value = value.as_ref();
                         let value_len = try!(i32::from_usize(value.len()));
-                        try!(buf.write_i32::<BigEndian>(value_len));
+                        buf.write_i32(value_len);
                         buf.extend_from_slice(value);
                     }
                 }
             }
 
             let num_result_formats = try!(u16::from_usize(self.result_formats.len()));
-            try!(buf.write_u16::<BigEndian>(num_result_formats));
+            buf.write_u16(num_result_formats);
             for &result_format in self.result_formats {
-                try!(buf.write_i16::<BigEndian>(result_format));
+                buf.write_i16(result_format);
             }
 
             Ok(())
@@ -70,17 +136,160 @@ impl<'a, T> Message for Bind<'a, T>
     }
 }
 
+#[cfg(feature = "std")]
+impl<'a, T> MessageVectored for Bind<'a, T>
+    where T: AsRef<[u8]>
+{
+    fn write_vectored<'b>(&'b self, scratch: &'b mut BytesMut) -> Result<Vec<IoSlice<'b>>, Error> {
+        scratch.clear();
+        scratch.put_u8(b'B');
+        scratch.extend_from_slice(&[0; 4]); // length, patched in below
+
+        try!(scratch.write_cstr(self.portal));
+        try!(scratch.write_cstr(self.statement));
+
+        let num_formats = try!(u16::from_usize(self.formats.len()));
+        scratch.write_u16(num_formats);
+        for &format in self.formats {
+            scratch.write_i16(format);
+        }
+
+        let num_values = try!(u16::from_usize(self.values.len()));
+        scratch.write_u16(num_values);
+
+        let head_end = scratch.len();
+
+        let num_result_formats = try!(u16::from_usize(self.result_formats.len()));
+        scratch.write_u16(num_result_formats);
+        for &result_format in self.result_formats {
+            scratch.write_i16(result_format);
+        }
+
+        let tail_end = scratch.len();
+
+        let mut payload_len = 0;
+        for value in self.values {
+            match *value {
+                None => scratch.write_i32(-1),
+                Some(ref value) => {
+                    let value = value.as_ref();
+                    let value_len = try!(i32::from_usize(value.len()));
+                    scratch.write_i32(value_len);
+                    payload_len += value.len();
+                }
+            }
+        }
+
+        let total = scratch.len() - 5 + payload_len;
+        let size = try!(i32::from_usize(total + 4));
+        patch_len(scratch, 1, size);
+
+        let mut slices = Vec::with_capacity(2 + 2 * self.values.len());
+        slices.push(IoSlice::new(&scratch[..head_end]));
+
+        let mut prefix_start = tail_end;
+        for value in self.values {
+            slices.push(IoSlice::new(&scratch[prefix_start..prefix_start + 4]));
+            prefix_start += 4;
+            if let Some(ref value) = *value {
+                slices.push(IoSlice::new(value.as_ref()));
+            }
+        }
+
+        slices.push(IoSlice::new(&scratch[head_end..tail_end]));
+
+        Ok(slices)
+    }
+}
+
+/// A `Bind` builder that writes each parameter value through a caller
+/// supplied closure rather than requiring it to already exist as a
+/// contiguous byte slice.
+///
+/// This is an alternative to [`Bind`] for callers that want to encode a
+/// value (e.g. an integer or a large blob read incrementally) directly into
+/// the outgoing buffer instead of allocating an intermediate `Vec<u8>` for
+/// every parameter.
+pub struct BindBuilder<'a> {
+    pub portal: &'a str,
+    pub statement: &'a str,
+    pub formats: &'a [i16],
+    pub result_formats: &'a [i16],
+}
+
+impl<'a> BindBuilder<'a> {
+    pub fn write<I, F>(&self, values: I, buf: &mut BytesMut) -> Result<(), Error>
+        where I: IntoIterator<Item = F>,
+              F: FnOnce(&mut BytesMut) -> Result<IsNull, Error>
+    {
+        buf.put_u8(b'B');
+
+        write_body(buf, |buf| {
+            try!(buf.write_cstr(self.portal));
+            try!(buf.write_cstr(self.statement));
+
+            let num_formats = try!(u16::from_usize(self.formats.len()));
+            buf.write_u16(num_formats);
+            for &format in self.formats {
+                buf.write_i16(format);
+            }
+
+            let num_values_base = buf.len();
+            buf.extend_from_slice(&[0; 2]);
+            let mut num_values = 0u16;
+
+            for value in values {
+                let len_base = buf.len();
+                buf.extend_from_slice(&[0; 4]);
+                let value_base = buf.len();
+
+                match try!(value(buf)) {
+                    IsNull::No => {
+                        let value_len = try!(i32::from_usize(buf.len() - value_base));
+                        patch_len(buf, len_base, value_len);
+                    }
+                    IsNull::Yes => {
+                        buf.truncate(value_base);
+                        patch_len(buf, len_base, -1);
+                    }
+                }
+
+                num_values = try!(u16::from_usize(num_values as usize + 1));
+            }
+            BigEndian::write_u16(&mut buf[num_values_base..num_values_base + 2], num_values);
+
+            let num_result_formats = try!(u16::from_usize(self.result_formats.len()));
+            buf.write_u16(num_result_formats);
+            for &result_format in self.result_formats {
+                buf.write_i16(result_format);
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// Writes a message consisting of just a length header and the given code,
+/// with no type byte. This is the shape shared by `SSLRequest` and
+/// `GSSENCRequest`.
+fn write_code_only(buf: &mut BytesMut, code: i32) -> Result<(), Error> {
+    write_body(buf, |buf| {
+        buf.write_i32(code);
+        Ok(())
+    })
+}
+
 pub struct CancelRequest {
     pub process_id: i32,
     pub secret_key: i32,
 }
 
 impl Message for CancelRequest {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>> {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
         write_body(buf, |buf| {
-            try!(buf.write_i32::<BigEndian>(80877102));
-            try!(buf.write_i32::<BigEndian>(self.process_id));
-            try!(buf.write_i32::<BigEndian>(self.secret_key));
+            buf.write_i32(80877102);
+            buf.write_i32(self.process_id);
+            buf.write_i32(self.secret_key);
             Ok(())
         })
     }
@@ -92,10 +301,10 @@ pub struct Close<'a> {
 }
 
 impl<'a> Message for Close<'a> {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>> {
-        buf.push(b'C');
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'C');
         write_body(buf, |buf| {
-            buf.push(self.variant);
+            buf.put_u8(self.variant);
             buf.write_cstr(self.name)
         })
     }
@@ -106,8 +315,8 @@ pub struct CopyData<'a> {
 }
 
 impl<'a> Message for CopyData<'a> {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>> {
-        buf.push(b'd');
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'd');
         write_body(buf, |buf| {
             buf.extend_from_slice(self.data);
             Ok(())
@@ -115,11 +324,23 @@ impl<'a> Message for CopyData<'a> {
     }
 }
 
+#[cfg(feature = "std")]
+impl<'a> MessageVectored for CopyData<'a> {
+    fn write_vectored<'b>(&'b self, scratch: &'b mut BytesMut) -> Result<Vec<IoSlice<'b>>, Error> {
+        scratch.clear();
+        scratch.put_u8(b'd');
+        let len = try!(i32::from_usize(self.data.len() + 4));
+        scratch.write_i32(len);
+
+        Ok(vec![IoSlice::new(&scratch[..]), IoSlice::new(self.data)])
+    }
+}
+
 pub struct CopyDone;
 
 impl Message for CopyDone {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>> {
-        buf.push(b'c');
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'c');
         write_body(buf, |_| Ok(()))
     }
 }
@@ -129,8 +350,8 @@ pub struct CopyFail<'a> {
 }
 
 impl<'a> Message for CopyFail<'a> {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>> {
-        buf.push(b'f');
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'f');
         write_body(buf, |buf| buf.write_cstr(self.message))
     }
 }
@@ -141,10 +362,10 @@ pub struct Describe<'a> {
 }
 
 impl<'a> Message for Describe<'a> {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>> {
-        buf.push(b'D');
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'D');
         write_body(buf, |buf| {
-            buf.push(self.variant);
+            buf.put_u8(self.variant);
             buf.write_cstr(self.name)
         })
     }
@@ -156,16 +377,33 @@ pub struct Execute<'a> {
 }
 
 impl<'a> Message for Execute<'a> {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>> {
-        buf.push(b'E');
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'E');
         write_body(buf, |buf| {
             try!(buf.write_cstr(self.portal));
-            try!(buf.write_i32::<BigEndian>(self.max_rows));
+            buf.write_i32(self.max_rows);
             Ok(())
         })
     }
 }
 
+pub struct Flush;
+
+impl Message for Flush {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'H');
+        write_body(buf, |_| Ok(()))
+    }
+}
+
+pub struct GSSENCRequest;
+
+impl Message for GSSENCRequest {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        write_code_only(buf, 80877104)
+    }
+}
+
 pub struct Parse<'a> {
     pub name: &'a str,
     pub query: &'a str,
@@ -173,46 +411,149 @@ pub struct Parse<'a> {
 }
 
 impl<'a> Message for Parse<'a> {
-    fn write(&self, buf: &mut Vec<u8>) -> Result<(), Box<Error>> {
-        buf.push(b'P');
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'P');
         write_body(buf, |buf| {
             try!(buf.write_cstr(self.name));
             try!(buf.write_cstr(self.query));
             let num_param_types = try!(u16::from_usize(self.param_types.len()));
-            try!(buf.write_u16::<BigEndian>(num_param_types));
+            buf.write_u16(num_param_types);
             for &param_type in self.param_types {
-                try!(buf.write_u32::<BigEndian>(param_type));
+                buf.write_u32(param_type);
             }
             Ok(())
         })
     }
 }
 
+pub struct PasswordMessage<'a> {
+    pub password: &'a str,
+}
+
+impl<'a> Message for PasswordMessage<'a> {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'p');
+        write_body(buf, |buf| buf.write_cstr(self.password))
+    }
+}
+
+pub struct Query<'a> {
+    pub query: &'a str,
+}
+
+impl<'a> Message for Query<'a> {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'Q');
+        write_body(buf, |buf| buf.write_cstr(self.query))
+    }
+}
+
+pub struct SASLInitialResponse<'a> {
+    pub mechanism: &'a str,
+    pub data: Option<&'a [u8]>,
+}
+
+impl<'a> Message for SASLInitialResponse<'a> {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'p');
+        write_body(buf, |buf| {
+            try!(buf.write_cstr(self.mechanism));
+            match self.data {
+                None => buf.write_i32(-1),
+                Some(data) => {
+                    let len = try!(i32::from_usize(data.len()));
+                    buf.write_i32(len);
+                    buf.extend_from_slice(data);
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+pub struct SASLResponse<'a> {
+    pub data: &'a [u8],
+}
+
+impl<'a> Message for SASLResponse<'a> {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'p');
+        write_body(buf, |buf| {
+            buf.extend_from_slice(self.data);
+            Ok(())
+        })
+    }
+}
+
+pub struct SSLRequest;
+
+impl Message for SSLRequest {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        write_code_only(buf, 80877103)
+    }
+}
+
+pub struct StartupMessage<'a> {
+    pub parameters: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> Message for StartupMessage<'a> {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        write_body(buf, |buf| {
+            buf.write_i32(0x00030000);
+            for &(key, value) in self.parameters {
+                try!(buf.write_cstr(key));
+                try!(buf.write_cstr(value));
+            }
+            buf.put_u8(0);
+            Ok(())
+        })
+    }
+}
+
+pub struct Sync;
+
+impl Message for Sync {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'S');
+        write_body(buf, |_| Ok(()))
+    }
+}
+
+pub struct Terminate;
+
+impl Message for Terminate {
+    fn write(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        buf.put_u8(b'X');
+        write_body(buf, |_| Ok(()))
+    }
+}
+
 trait WriteCStr {
-    fn write_cstr(&mut self, s: &str) -> Result<(), Box<Error>>;
+    fn write_cstr(&mut self, s: &str) -> Result<(), Error>;
 }
 
-impl WriteCStr for Vec<u8> {
-    fn write_cstr(&mut self, s: &str) -> Result<(), Box<Error>> {
+impl WriteCStr for BytesMut {
+    fn write_cstr(&mut self, s: &str) -> Result<(), Error> {
         if s.as_bytes().contains(&0) {
-            return Err("string contains embedded null".into());
+            return Err(Error::EmbeddedNul);
         }
         self.extend_from_slice(s.as_bytes());
-        self.push(0);
+        self.put_u8(0);
         Ok(())
     }
 }
 
 trait FromUsize: Sized {
-    fn from_usize(x: usize) -> Result<Self, Box<Error>>;
+    fn from_usize(x: usize) -> Result<Self, Error>;
 }
 
 macro_rules! from_usize {
     ($t:ty) => {
         impl FromUsize for $t {
-            fn from_usize(x: usize) -> Result<$t, Box<Error>> {
+            fn from_usize(x: usize) -> Result<$t, Error> {
                 if x > <$t>::max_value() as usize {
-                    Err("value too large to transmit".into())
+                    Err(Error::TooLarge)
                 } else {
                     Ok(x as $t)
                 }
@@ -223,3 +564,32 @@ macro_rules! from_usize {
 
 from_usize!(u16);
 from_usize!(i32);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_write_vectored_matches_write() {
+        let values: Vec<Option<&[u8]>> = vec![Some(b"hello" as &[u8]), None, Some(b"" as &[u8])];
+        let bind = Bind {
+            portal: "my_portal",
+            statement: "my_statement",
+            formats: &[0, 1],
+            values: &values,
+            result_formats: &[1],
+        };
+
+        let mut expected = BytesMut::new();
+        bind.write(&mut expected).unwrap();
+
+        let mut scratch = BytesMut::new();
+        let slices = bind.write_vectored(&mut scratch).unwrap();
+        let mut actual = Vec::new();
+        for slice in &slices {
+            actual.extend_from_slice(slice);
+        }
+
+        assert_eq!(&actual[..], &expected[..]);
+    }
+}
@@ -0,0 +1,5 @@
+#[cfg(feature = "std")]
+pub mod backend;
+pub mod frontend;
+
+pub type Oid = u32;
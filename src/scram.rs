@@ -0,0 +1,193 @@
+//! An implementation of the SCRAM-SHA-256 SASL authentication mechanism
+//! described in [RFC 5802](https://tools.ietf.org/html/rfc5802) and
+//! [RFC 7677](https://tools.ietf.org/html/rfc7677).
+
+use base64;
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2;
+use rand::{self, Rng};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::iter;
+use std::str;
+
+const NONCE_LENGTH: usize = 18;
+
+fn nonce() -> String {
+    let mut rng = rand::thread_rng();
+    iter::repeat(())
+        .map(|()| rng.gen_range(0x21u8, 0x7e))
+        .filter(|&b| b != b',')
+        .map(|b| b as char)
+        .take(NONCE_LENGTH)
+        .collect()
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC can take a key of any length");
+    mac.input(message);
+    mac.result().code().to_vec()
+}
+
+fn sha256(value: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::default();
+    hasher.input(value);
+    hasher.result().to_vec()
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b.iter()).map(|(&a, &b)| a ^ b).collect()
+}
+
+/// The state of an in-progress SCRAM-SHA-256 authentication exchange.
+///
+/// The flow is: construct with `new`, send `message()` to the server as the
+/// `SASLInitialResponse`, feed the server's `AuthenticationSASLContinue` data
+/// to `update`, send the resulting `message()` as the `SASLResponse`, and
+/// finally feed the server's `AuthenticationSASLFinal` data to `finish` to
+/// verify the server's identity.
+pub struct ScramSha256 {
+    message: Vec<u8>,
+    client_nonce: String,
+    password: Vec<u8>,
+    server_signature: Option<Vec<u8>>,
+}
+
+impl ScramSha256 {
+    /// Begins a new SCRAM-SHA-256 authentication exchange with the given
+    /// password.
+    pub fn new(password: &[u8]) -> ScramSha256 {
+        ScramSha256::with_nonce(password, &nonce())
+    }
+
+    fn with_nonce(password: &[u8], nonce: &str) -> ScramSha256 {
+        let message = format!("n,,n=,r={}", nonce).into_bytes();
+        ScramSha256 {
+            message,
+            client_nonce: nonce.to_owned(),
+            password: password.to_owned(),
+            server_signature: None,
+        }
+    }
+
+    /// Returns the message that should be sent to the server.
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// Updates the state with the server's first message, computing the
+    /// client final message to be sent in response.
+    pub fn update(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let server_first = try!(str::from_utf8(data));
+
+        let parsed_nonce = try!(find_field(server_first, "r")
+            .ok_or_else::<Box<dyn Error>, _>(|| "server did not send a nonce".into()));
+        if !parsed_nonce.starts_with(&self.client_nonce) {
+            return Err("server nonce did not start with client nonce".into());
+        }
+
+        let salt = try!(find_field(server_first, "s")
+            .ok_or_else::<Box<dyn Error>, _>(|| "server did not send a salt".into()));
+        let salt = try!(base64::decode(salt));
+
+        let iterations: u32 = {
+            let iterations = try!(find_field(server_first, "i")
+                .ok_or_else::<Box<dyn Error>, _>(|| "server did not send an iteration count".into()));
+            try!(iterations.parse())
+        };
+
+        let mut salted_password = [0; 32];
+        pbkdf2::<Hmac<Sha256>>(&self.password, &salt, iterations as usize, &mut salted_password);
+
+        let client_key = hmac(&salted_password, b"Client Key");
+        let stored_key = sha256(&client_key);
+
+        let client_final_without_proof = format!("c=biws,r={}", parsed_nonce);
+        let client_first_bare = format!("n=,r={}", self.client_nonce);
+        let auth_message = format!("{},{},{}", client_first_bare, server_first, client_final_without_proof);
+
+        let client_signature = hmac(&stored_key, auth_message.as_bytes());
+        let client_proof = xor(&client_key, &client_signature);
+
+        let server_key = hmac(&salted_password, b"Server Key");
+        self.server_signature = Some(hmac(&server_key, auth_message.as_bytes()));
+
+        self.message = format!(
+            "{},p={}",
+            client_final_without_proof,
+            base64::encode(&client_proof)
+        ).into_bytes();
+
+        Ok(())
+    }
+
+    /// Verifies the server's final message, completing the exchange.
+    pub fn finish(self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let server_final = try!(str::from_utf8(data));
+
+        let verifier = try!(find_field(server_final, "v")
+            .ok_or_else::<Box<dyn Error>, _>(|| "server did not send a signature".into()));
+        let verifier = try!(base64::decode(verifier));
+
+        let server_signature = try!(self.server_signature
+            .ok_or_else::<Box<dyn Error>, _>(|| "finish called before update".into()));
+        if verifier != server_signature {
+            return Err("SCRAM verification failed".into());
+        }
+
+        Ok(())
+    }
+}
+
+fn find_field<'a>(message: &'a str, name: &str) -> Option<&'a str> {
+    message
+        .split(',')
+        .find(|field| field.starts_with(name) && field[name.len()..].starts_with('='))
+        .map(|field| &field[name.len() + 1..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixed nonce, salt, and iteration count taken from the SCRAM-SHA-256
+    // exchange in RFC 7677 section 3; the client-final `p=` and server
+    // signature below were computed against this crate's own (username-less)
+    // `client-first-bare`, so they won't match the RFC's published values.
+    const SERVER_FIRST: &[u8] =
+        b"r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096";
+    const CLIENT_FINAL: &str =
+        "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF,\
+         p=r83GP156MVl1CNRJy34ZMKc6mFhW0fbfdtxQ0COmIAc=";
+    const SERVER_SIGNATURE: &str = "9qac/RAPwtgF7hQ1aLcbvK1g9hmylPPCAWiKRzQHu5o=";
+
+    #[test]
+    fn update_computes_expected_client_final_message() {
+        let mut scram = ScramSha256::with_nonce(b"pencil", "rOprNGfwEbeRWgbNEkqO");
+        scram.update(SERVER_FIRST).unwrap();
+        assert_eq!(str::from_utf8(scram.message()).unwrap(), CLIENT_FINAL);
+    }
+
+    #[test]
+    fn finish_accepts_matching_server_signature() {
+        let mut scram = ScramSha256::with_nonce(b"pencil", "rOprNGfwEbeRWgbNEkqO");
+        scram.update(SERVER_FIRST).unwrap();
+        let server_final = format!("v={}", SERVER_SIGNATURE);
+        scram.finish(server_final.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn finish_rejects_wrong_server_signature() {
+        let mut scram = ScramSha256::with_nonce(b"pencil", "rOprNGfwEbeRWgbNEkqO");
+        scram.update(SERVER_FIRST).unwrap();
+        let server_final = format!("v={}", base64::encode(b"not the right signature!!!!!!!!"));
+        assert!(scram.finish(server_final.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn finish_before_update_errors_instead_of_panicking() {
+        let scram = ScramSha256::with_nonce(b"pencil", "rOprNGfwEbeRWgbNEkqO");
+        let server_final = format!("v={}", SERVER_SIGNATURE);
+        assert!(scram.finish(server_final.as_bytes()).is_err());
+    }
+}